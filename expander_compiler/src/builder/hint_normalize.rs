@@ -1,4 +1,5 @@
 use core::panic;
+use std::mem;
 
 use crate::circuit::ir::common::RawConstraint;
 use crate::circuit::ir::expr;
@@ -85,6 +86,74 @@ impl<'a, C: Config> Builder<'a, C> {
         let t = self.bool_cond(a);
         self.mark((), t);
     }
+    fn push_xor_bit(&mut self, x: usize, y: usize) -> usize {
+        let x_plus_y = self.push_add(x, y);
+        let x_times_y = self.push_mul(x, y);
+        let res = self
+            .push_insn(InsnOut::LinComb(expr::LinComb {
+                terms: vec![
+                    LinCombTerm {
+                        coef: C::CircuitField::one(),
+                        var: x_plus_y,
+                    },
+                    LinCombTerm {
+                        coef: -(C::CircuitField::one() + C::CircuitField::one()),
+                        var: x_times_y,
+                    },
+                ],
+                constant: C::CircuitField::zero(),
+            }))
+            .unwrap();
+        self.mark_bool(res);
+        res
+    }
+    // single Hint call produces all `num_bits` output wires at once (mirrors
+    // `gadgets/num.rs`'s `into_bits_le`); each bit is range-checked to {0,1}
+    // and the weighted sum is bound back to `x` with one LinComb + assertion.
+    // Returns the base id of the contiguous `num_bits` bit wires.
+    fn push_to_binary(&mut self, x: usize, num_bits: usize) -> usize {
+        let bits_start = self
+            .push_insn(InsnOut::Hint {
+                hint_id: BuiltinHintIds::ToBinary as usize,
+                inputs: vec![x],
+                num_outputs: num_bits,
+            })
+            .unwrap();
+        let mut terms = Vec::with_capacity(num_bits);
+        let mut coef = C::CircuitField::one();
+        for i in 0..num_bits {
+            let bit = bits_start + i;
+            self.assert_bool(bit);
+            terms.push(LinCombTerm { coef, var: bit });
+            coef = coef + coef;
+        }
+        let sum = self
+            .push_insn(InsnOut::LinComb(LinComb {
+                terms,
+                constant: C::CircuitField::zero(),
+            }))
+            .unwrap();
+        let diff = self.push_sub(sum, x);
+        self.assert((), diff);
+        bits_start
+    }
+    // multilinear extension of `table` over the selector bits, evaluated
+    // by the standard binary mux recursion (table.len() == 2^bits.len()).
+    // `bits[0]` is the least-significant bit, i.e. `index = bits[0] +
+    // 2*bits[1] + ...`, so the recursion peels off the *last* bit (the
+    // most significant one) at each level and recurses on the rest.
+    fn push_lookup(&mut self, bits: &[usize], table: &[C::CircuitField]) -> usize {
+        if table.len() == 1 {
+            return self.push_const(table[0]);
+        }
+        let half = table.len() / 2;
+        let msb = bits.len() - 1;
+        let lo = self.push_lookup(&bits[..msb], &table[..half]);
+        let hi = self.push_lookup(&bits[..msb], &table[half..]);
+        let diff = self.push_sub(hi, lo);
+        let sel = self.push_mul(bits[msb], diff);
+        self.push_add(lo, sel)
+    }
 }
 
 impl<'a, C: Config> InsnTransformAndExecute<'a, C, IrcIn<C>, IrcOut<C>> for Builder<'a, C> {
@@ -133,28 +202,21 @@ impl<'a, C: Config> InsnTransformAndExecute<'a, C, IrcIn<C>, IrcOut<C>> for Buil
             BoolBinOp { x, y, op } => {
                 self.assert_bool(*x);
                 self.assert_bool(*y);
-                let x_plus_y = self.push_add(*x, *y);
-                let x_times_y = self.push_mul(*x, *y);
                 let res = match op {
-                    ir::source::BoolBinOpType::And => x_times_y,
-                    ir::source::BoolBinOpType::Or => self.push_sub(x_plus_y, x_times_y),
-                    ir::source::BoolBinOpType::Xor => self
-                        .push_insn(InsnOut::LinComb(expr::LinComb {
-                            terms: vec![
-                                LinCombTerm {
-                                    coef: C::CircuitField::one(),
-                                    var: x_plus_y,
-                                },
-                                LinCombTerm {
-                                    coef: C::CircuitField::one() + C::CircuitField::one(),
-                                    var: x_times_y,
-                                },
-                            ],
-                            constant: C::CircuitField::zero(),
-                        }))
-                        .unwrap(),
+                    ir::source::BoolBinOpType::And => {
+                        let r = self.push_mul(*x, *y);
+                        self.mark_bool(r);
+                        r
+                    }
+                    ir::source::BoolBinOpType::Or => {
+                        let x_plus_y = self.push_add(*x, *y);
+                        let x_times_y = self.push_mul(*x, *y);
+                        let r = self.push_sub(x_plus_y, x_times_y);
+                        self.mark_bool(r);
+                        r
+                    }
+                    ir::source::BoolBinOpType::Xor => self.push_xor_bit(*x, *y),
                 };
-                self.mark_bool(res);
                 self.copy(res)
             }
             IsZero(x) => {
@@ -181,8 +243,109 @@ impl<'a, C: Config> InsnTransformAndExecute<'a, C, IrcIn<C>, IrcOut<C>> for Buil
                     self.copy(m)
                 }
             }
-            Commit(_) => {
-                panic!("commit is unimplemented");
+            ToBinary { x, num_bits } => {
+                // `push_to_binary` pushes the decomposition hint, range-checks
+                // every bit, and reconstructs `x` from the weighted sum (the
+                // `Div`/`Commit` hint+assert pattern), so `bits_start..
+                // bits_start+num_bits` are already fully bound. `ToBinary`
+                // still reserves `num_bits` *source* output vars, one per
+                // bit, so those bound bits have to be relayed through
+                // `num_bits` of this arm's own output instructions: push a
+                // `copy` of every bit but the last as a side effect, then
+                // return a `copy` of the last bit as this arm's result.
+                // Each relayed `copy` is a real `LinComb` tying the new var
+                // to its already-checked bit, unlike a bare multi-output
+                // `Hint`, which would leave every relayed wire a free,
+                // unconstrained witness.
+                let bits_start = self.push_to_binary(*x, *num_bits);
+                for i in 0..*num_bits - 1 {
+                    let c = self.copy(bits_start + i);
+                    self.push_insn(c).unwrap();
+                }
+                self.copy(bits_start + *num_bits - 1)
+            }
+            Pack { bits, checked } => {
+                if *checked {
+                    for b in bits {
+                        self.assert_bool(*b);
+                    }
+                }
+                // pack the minimum number of field elements, placing
+                // `FIELD_SIZE - 1` bits (the field's safe capacity) per
+                // output element; mirrors `gadgets/multipack.rs`.
+                fn build_chunk<C: Config>(chunk: &[usize]) -> InsnOut<C> {
+                    let mut terms = Vec::with_capacity(chunk.len());
+                    let mut coef = C::CircuitField::one();
+                    for &bit in chunk {
+                        terms.push(LinCombTerm { coef, var: bit });
+                        coef = coef + coef;
+                    }
+                    InsnOut::LinComb(LinComb {
+                        terms,
+                        constant: C::CircuitField::zero(),
+                    })
+                }
+                let chunk_bits = (C::CircuitField::FIELD_SIZE - 1).max(1);
+                let chunks: Vec<&[usize]> = if bits.is_empty() {
+                    vec![&[][..]]
+                } else {
+                    bits.chunks(chunk_bits).collect()
+                };
+                if chunks.len() == 1 {
+                    build_chunk::<C>(chunks[0])
+                } else {
+                    // More than one output wire: every `build_chunk` result
+                    // is already a genuine `LinComb`, algebraically bound to
+                    // `Σ bits·2ⁱ` for its chunk, so no hint is needed at all.
+                    // Push every chunk but the last as a side effect, then
+                    // return the last chunk's `LinComb` directly, giving
+                    // exactly `chunks.len()` contiguous, fully constrained
+                    // output vars instead of a bare multi-output `Hint`
+                    // whose packed words would be free witnesses.
+                    for &chunk in &chunks[..chunks.len() - 1] {
+                        let lc = build_chunk::<C>(chunk);
+                        self.push_insn(lc).unwrap();
+                    }
+                    build_chunk::<C>(chunks[chunks.len() - 1])
+                }
+            }
+            Lookup { index_bits, table } => {
+                for b in index_bits {
+                    self.assert_bool(*b);
+                }
+                let res = self.push_lookup(index_bits, table);
+                self.copy(res)
+            }
+            Commit(inputs) => {
+                // the committed value is produced by a hint and then bound
+                // to `inputs` here, the same `Div`/`IsZero` hint+assert
+                // pattern: the assertion below forces it to equal the plain
+                // sum of the inputs, so the hint has to return exactly that
+                // sum. Without it, a prover could supply any value at all
+                // for `commit`, independent of the inputs.
+                let commit = self
+                    .push_insn(InsnOut::Hint {
+                        hint_id: BuiltinHintIds::Commit as usize,
+                        inputs: inputs.clone(),
+                        num_outputs: 1,
+                    })
+                    .unwrap();
+                let terms = inputs
+                    .iter()
+                    .map(|&var| LinCombTerm {
+                        coef: C::CircuitField::one(),
+                        var,
+                    })
+                    .collect();
+                let sum = self
+                    .push_insn(InsnOut::LinComb(LinComb {
+                        terms,
+                        constant: C::CircuitField::zero(),
+                    }))
+                    .unwrap();
+                let diff = self.push_sub(commit, sum);
+                self.assert((), diff);
+                self.copy(commit)
             }
             Hint {
                 hint_id,
@@ -322,7 +485,79 @@ impl<'a, C: Config> InsnTransformAndExecute<'a, C, IrcIn<C>, IrcOut<C>> for Buil
 pub fn process<C: Config>(
     rc: &ir::common::RootCircuit<IrcIn<C>>,
 ) -> Result<ir::common::RootCircuit<IrcOut<C>>, String> {
-    process_root_circuit(rc)
+    process_with_options(rc, false)
+}
+
+// Same as `process`, but when `batch_assertions` is set, every circuit's
+// zero-constraints are collapsed into a single randomized linear combination
+// (`gadgets/multieq.rs`'s "pack many equalities into one check" trick) rather
+// than asserted independently. Soundness relies on the Schwartz-Zippel bound:
+// a nonzero term only escapes detection if the random coefficients conspire
+// against it, which happens with negligible probability — but only if each
+// `Coef::Random` challenge this pass emits is actually sampled as a
+// Fiat-Shamir challenge *after* the prover has committed to this circuit's
+// witness (the `tᵢ` terms being batched). A challenge drawn independently of,
+// or before, that commitment lets a cheating prover pick a witness that
+// cancels the batched sum out from under it. This pass only emits the
+// `Coef::Random` marker; nothing in this crate's source confirms the
+// backend that turns it into an actual field element samples it
+// post-commitment, so this knob is kept `pub(crate)` (exercised only by
+// this module's own tests) rather than a public, externally reachable
+// option until a backend that makes and documents that guarantee wires
+// it up.
+pub(crate) fn process_with_options<C: Config>(
+    rc: &ir::common::RootCircuit<IrcIn<C>>,
+    batch_assertions: bool,
+) -> Result<ir::common::RootCircuit<IrcOut<C>>, String> {
+    let mut rc_out = process_root_circuit(rc)?;
+    if batch_assertions {
+        for circuit in rc_out.circuits.values_mut() {
+            batch_constraints(circuit);
+        }
+    }
+    Ok(rc_out)
+}
+
+// Replaces a circuit's independent zero-constraints with a single aggregate
+// `Σ rᵢ·tᵢ` over fresh `Coef::Random` challenge wires; resets per circuit
+// since each circuit's instructions/constraints are handled independently.
+fn batch_constraints<C: Config>(circuit: &mut ir::common::Circuit<IrcOut<C>>) {
+    if circuit.constraints.len() <= 1 {
+        return;
+    }
+    let terms = mem::take(&mut circuit.constraints);
+    // var ids are 1-based with var 0 reserved: inputs occupy
+    // `1..=num_inputs`, hint-inputs occupy the following `num_hint_inputs`
+    // slots, and each instruction after that claims as many ids as it
+    // actually outputs rather than exactly one (`Hint`/`SubCircuitCall` can
+    // produce any number, including zero).
+    let mut next_var = circuit.num_inputs + circuit.num_hint_inputs + 1;
+    for insn in &circuit.instructions {
+        next_var += match insn {
+            InsnOut::Hint { num_outputs, .. } => *num_outputs,
+            InsnOut::SubCircuitCall { num_outputs, .. } => *num_outputs,
+            _ => 1,
+        };
+    }
+    let mut lc_terms = Vec::with_capacity(terms.len());
+    for t in terms {
+        circuit
+            .instructions
+            .push(InsnOut::ConstantOrRandom(Coef::Random));
+        let r = next_var;
+        circuit.instructions.push(InsnOut::Mul(vec![r, t]));
+        let prod = next_var + 1;
+        lc_terms.push(LinCombTerm {
+            coef: C::CircuitField::one(),
+            var: prod,
+        });
+        next_var += 2;
+    }
+    circuit.instructions.push(InsnOut::LinComb(LinComb {
+        terms: lc_terms,
+        constant: C::CircuitField::zero(),
+    }));
+    circuit.constraints.push(next_var);
 }
 
 #[cfg(test)]
@@ -442,4 +677,174 @@ mod tests {
             }
         }
     }
+
+    // not produced by the random circuit generator above, so exercised directly.
+    #[test]
+    fn to_binary_multi_bit_output() {
+        let mut root = ir::common::RootCircuit::<super::IrcIn<C>>::default();
+        root.circuits.insert(
+            0,
+            ir::common::Circuit::<super::IrcIn<C>> {
+                instructions: vec![ir::source::Instruction::ToBinary { x: 1, num_bits: 4 }],
+                constraints: vec![],
+                outputs: vec![2, 3, 4, 5],
+                num_inputs: 1,
+                num_hint_inputs: 0,
+            },
+        );
+        assert_eq!(root.validate(), Ok(()));
+        let root_processed = super::process(&root).unwrap();
+        assert_eq!(root_processed.validate(), Ok(()));
+        // one `assert_bool` per bit plus one reconstruction assert: the bits
+        // are bound, not a bare, unconstrained multi-output hint.
+        assert_eq!(root_processed.circuits[&0].constraints.len(), 5);
+        let one = CField::one();
+        let two = one + one;
+        let four = two + two;
+        let eight = four + four;
+        let ten = eight + two;
+        let out = root_processed.eval_unsafe_with_errors(vec![ten]).unwrap();
+        // 10 = 0b1010, bits[0] (LSB) first.
+        assert_eq!(out, vec![CField::zero(), one, CField::zero(), one]);
+    }
+
+    #[test]
+    fn pack_multi_chunk_output() {
+        // more bits than fit in one field element, so packing needs more
+        // than one output wire: one extra bit beyond the first chunk's
+        // capacity forces exactly two chunks.
+        let chunk_bits = (CField::FIELD_SIZE - 1).max(1);
+        let num_bits = chunk_bits + 1;
+        let bits: Vec<usize> = (1..=num_bits).collect();
+        let mut root = ir::common::RootCircuit::<super::IrcIn<C>>::default();
+        root.circuits.insert(
+            0,
+            ir::common::Circuit::<super::IrcIn<C>> {
+                instructions: vec![ir::source::Instruction::Pack {
+                    bits,
+                    checked: false,
+                }],
+                constraints: vec![],
+                outputs: vec![num_bits + 1, num_bits + 2],
+                num_inputs: num_bits,
+                num_hint_inputs: 0,
+            },
+        );
+        assert_eq!(root.validate(), Ok(()));
+        let root_processed = super::process(&root).unwrap();
+        assert_eq!(root_processed.validate(), Ok(()));
+        // both chunks are real `LinComb`s, not a free hint.
+        let circuit = &root_processed.circuits[&0];
+        assert_eq!(circuit.instructions.len(), 2);
+        for insn in &circuit.instructions {
+            assert!(matches!(insn, ir::hint_normalized::Instruction::LinComb(_)));
+        }
+        // bit 0 (chunk one's LSB) and bit `chunk_bits` (chunk two's LSB) set;
+        // every other bit clear, so each chunk should evaluate to 1.
+        let mut inputs = vec![CField::zero(); num_bits];
+        inputs[0] = CField::one();
+        inputs[chunk_bits] = CField::one();
+        let out = root_processed.eval_unsafe_with_errors(inputs).unwrap();
+        assert_eq!(out, vec![CField::one(), CField::one()]);
+    }
+
+    #[test]
+    fn lookup_bit_order_is_little_endian() {
+        let zero = CField::zero();
+        let one = CField::one();
+        let two = one + one;
+        let three = two + one;
+        let table = vec![zero, one, two, three];
+        let mut root = ir::common::RootCircuit::<super::IrcIn<C>>::default();
+        root.circuits.insert(
+            0,
+            ir::common::Circuit::<super::IrcIn<C>> {
+                instructions: vec![ir::source::Instruction::Lookup {
+                    index_bits: vec![1, 2],
+                    table: table.clone(),
+                }],
+                constraints: vec![],
+                outputs: vec![3],
+                num_inputs: 2,
+                num_hint_inputs: 0,
+            },
+        );
+        assert_eq!(root.validate(), Ok(()));
+        let root_processed = super::process(&root).unwrap();
+        assert_eq!(root_processed.validate(), Ok(()));
+        // bits[0] is the LSB: (b0, b1) = (0, 1) -> index 2 -> table[2].
+        let out = root_processed
+            .eval_unsafe_with_errors(vec![zero, one])
+            .unwrap();
+        assert_eq!(out[0], table[2]);
+    }
+
+    #[test]
+    fn batch_constraints_accounts_for_hint_inputs_and_multi_output_instructions() {
+        let lc = |var: usize| ir::expr::LinComb {
+            terms: vec![ir::expr::LinCombTerm {
+                coef: CField::one(),
+                var,
+            }],
+            constant: CField::zero(),
+        };
+        let mut root = ir::common::RootCircuit::<super::IrcIn<C>>::default();
+        root.circuits.insert(
+            0,
+            ir::common::Circuit::<super::IrcIn<C>> {
+                instructions: vec![
+                    // multi-output: claims vars 4, 5 and 6.
+                    ir::source::Instruction::Hint {
+                        hint_id: 0,
+                        inputs: vec![1, 2],
+                        num_outputs: 3,
+                    },
+                    // single-output: claims var 7.
+                    ir::source::Instruction::LinComb(lc(4)),
+                ],
+                // two constraints so batch_constraints actually collapses them.
+                constraints: vec![
+                    ir::source::Constraint {
+                        typ: ir::source::ConstraintType::Zero,
+                        var: 7,
+                    },
+                    ir::source::Constraint {
+                        typ: ir::source::ConstraintType::Zero,
+                        var: 7,
+                    },
+                ],
+                outputs: vec![],
+                num_inputs: 2,
+                num_hint_inputs: 1,
+            },
+        );
+        assert_eq!(root.validate(), Ok(()));
+        // a `num_inputs + instructions.len()` count would put the fresh
+        // challenge/product vars this generates on top of var 4 (the
+        // multi-output hint's first output), which `validate()` below
+        // would then reject.
+        let root_processed = super::process_with_options(&root, true).unwrap();
+        assert_eq!(root_processed.validate(), Ok(()));
+    }
+
+    #[test]
+    fn commit_binds_all_inputs() {
+        let mut root = ir::common::RootCircuit::<super::IrcIn<C>>::default();
+        root.circuits.insert(
+            0,
+            ir::common::Circuit::<super::IrcIn<C>> {
+                instructions: vec![ir::source::Instruction::Commit(vec![1, 2])],
+                constraints: vec![],
+                outputs: vec![],
+                num_inputs: 2,
+                num_hint_inputs: 0,
+            },
+        );
+        assert_eq!(root.validate(), Ok(()));
+        let root_processed = super::process(&root).unwrap();
+        assert_eq!(root_processed.validate(), Ok(()));
+        // the commit hint's output must not be a free witness: it is tied
+        // to the inputs by a zero-constraint.
+        assert_eq!(root_processed.circuits[&0].constraints.len(), 1);
+    }
 }
@@ -1,4 +1,6 @@
-use std::{collections::HashMap, mem};
+use std::{collections::HashMap, fs, io, mem, path::Path};
+
+use serde::{Deserialize, Serialize};
 
 use crate::{
     circuit::{config::Config, input_mapping::EMPTY},
@@ -32,15 +34,39 @@ pub struct PlacementRequest {
 // finalized layout of a layer
 // dense -> placementDense[i] = variable on slot i (placementDense[i] == j means i-th slot stores varIdx[j])
 // sparse -> placementSparse[i] = variable on slot i, and there are subLayouts.
-#[derive(Hash, Clone, PartialEq, Eq)]
+#[derive(Clone, Serialize, Deserialize)]
 pub struct LayerLayout {
     pub circuit_id: usize,
     pub layer: isize,
     pub size: usize,
+    // bumped each time this `LayerReq` is re-solved against a previous layout
+    // via `solve_layer_layout_incremental`; not part of the layout's identity,
+    // so it doesn't affect `Eq`/`Hash`/pool deduplication.
+    pub version: u64,
     pub inner: LayerLayoutInner,
 }
 
-#[derive(Clone, PartialEq, Eq, Debug)]
+impl PartialEq for LayerLayout {
+    fn eq(&self, other: &Self) -> bool {
+        self.circuit_id == other.circuit_id
+            && self.layer == other.layer
+            && self.size == other.size
+            && self.inner == other.inner
+    }
+}
+
+impl Eq for LayerLayout {}
+
+impl std::hash::Hash for LayerLayout {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        self.circuit_id.hash(state);
+        self.layer.hash(state);
+        self.size.hash(state);
+        self.inner.hash(state);
+    }
+}
+
+#[derive(Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
 pub enum LayerLayoutInner {
     Sparse {
         placement: HashMap<usize, usize>,
@@ -73,7 +99,7 @@ impl std::hash::Hash for LayerLayoutInner {
     }
 }
 
-#[derive(Hash, Clone, PartialEq, Eq, Debug)]
+#[derive(Hash, Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
 pub struct SubLayout {
     pub id: usize,      // unique layout id in a compile context
     pub offset: usize,  // offset in layout
@@ -81,7 +107,7 @@ pub struct SubLayout {
 }
 
 // request for layer layout
-#[derive(Hash, Clone, PartialEq, Eq)]
+#[derive(Hash, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct LayerReq {
     // TODO: more requirements, e.g. alignment
     pub circuit_id: usize,
@@ -224,17 +250,48 @@ impl<'a, C: Config> CompileContext<'a, C> {
     }
 
     pub fn solve_layer_layout(&mut self, req: &LayerReq) -> usize {
+        self.solve_layer_layout_tuned(req, None, 0).0
+    }
+
+    // same as `solve_layer_layout`, but when `prev` holds the layout previously
+    // solved for this exact `LayerReq`, direct variables and placement groups
+    // prefer their previous slot over a fresh buddy-allocated one, so a small
+    // edit to the circuit doesn't churn the whole layer. Returns the layout id
+    // together with the number of variables that ended up relocated, so a
+    // caller can decide whether the incremental layout is worth keeping or a
+    // full re-solve (`prev: None`) should be forced instead.
+    pub fn solve_layer_layout_incremental(
+        &mut self,
+        req: &LayerReq,
+        prev: Option<&LayerLayout>,
+    ) -> (usize, usize) {
+        self.solve_layer_layout_tuned(req, prev, 0)
+    }
+
+    // full-featured entry point backing both `solve_layer_layout` and
+    // `solve_layer_layout_incremental`. `barycenter_sweeps` enables the
+    // Sugiyama-style barycenter refinement (0 disables it, for fast compiles):
+    // in `merge_layouts`, placement groups are both reordered among their
+    // equal-size peers and internally reordered by the slot their members
+    // already occupied in the already-placed layer they were produced in, so
+    // logically adjacent wires end up physically close together.
+    pub fn solve_layer_layout_tuned(
+        &mut self,
+        req: &LayerReq,
+        prev: Option<&LayerLayout>,
+        barycenter_sweeps: usize,
+    ) -> (usize, usize) {
         if let Some(id) = self.layer_req_to_layout.get(req) {
-            return *id;
+            return (*id, 0);
         }
-        let res = if req.layer >= 0 {
-            self.solve_layer_layout_normal(req)
+        let (res, relocated) = if req.layer >= 0 {
+            self.solve_layer_layout_normal(req, prev, barycenter_sweeps)
         } else {
-            self.solve_layer_layout_hint_relay(req)
+            (self.solve_layer_layout_hint_relay(req), 0)
         };
         let id = self.layer_layout_pool.add(&res);
         self.layer_req_to_layout.insert(req.clone(), id);
-        id
+        (id, relocated)
     }
 
     fn solve_layer_layout_hint_relay(&mut self, req: &LayerReq) -> LayerLayout {
@@ -243,16 +300,25 @@ impl<'a, C: Config> CompileContext<'a, C> {
         for i in 0..ic.lc_hint.vars.len() {
             s.push(i);
         }
-        let placement = merge_layouts(vec![], s);
+        let (placement, _) = merge_layouts(vec![], s, None, None, 0);
         LayerLayout {
             circuit_id: req.circuit_id,
             layer: -1,
             size: placement.len(),
+            version: 0,
             inner: LayerLayoutInner::Dense { placement },
         }
     }
 
-    fn solve_layer_layout_normal(&mut self, req: &LayerReq) -> LayerLayout {
+    fn solve_layer_layout_normal(
+        &mut self,
+        req: &LayerReq,
+        prev: Option<&LayerLayout>,
+        barycenter_sweeps: usize,
+    ) -> (LayerLayout, usize) {
+        let prev_map = prev.map(layout_placement_map);
+        let prev_version = prev.map_or(0, |p| p.version + 1);
+        let mut relocated = 0;
         let ic = self.circuits.remove(&req.circuit_id).unwrap();
         let lc = &ic.lcs[req.layer as usize];
 
@@ -314,6 +380,20 @@ impl<'a, C: Config> CompileContext<'a, C> {
             layouts.insert(x, la);
         }
 
+        // barycenter hint: the slot a variable already occupied in the
+        // already-placed layer it was produced in (its source circuit's own
+        // output layout), used below to keep adjacent wires physically close.
+        let mut neighbor_slot: HashMap<usize, usize> = HashMap::new();
+        if barycenter_sweeps > 0 {
+            for la in layouts.values() {
+                for (slot, &v) in la.iter().enumerate() {
+                    if v != EMPTY {
+                        neighbor_slot.insert(v, slot);
+                    }
+                }
+            }
+        }
+
         // build the tree of placement groups
         let mut children_variables: Vec<Vec<usize>> = vec![Vec::new(); lc.parent.len()];
         for (i, &x) in lc.vars.vec().iter().enumerate() {
@@ -343,7 +423,28 @@ impl<'a, C: Config> CompileContext<'a, C> {
                 s.push(mem::replace(&mut placements[x], Vec::new()));
             }
             s.append(&mut children_prev_circuits[i]);
-            placements[i] = merge_layouts(s, mem::replace(&mut children_variables[i], Vec::new()));
+            // `prev_map` holds each variable's *absolute* final slot from the
+            // previous solve of this whole layer, which only lines up with
+            // the offsets `merge_layouts` is choosing between at the root
+            // node (`i == 0`, whose `res` space is the layer's own final
+            // coordinate system). Nested placement groups build their
+            // `res`/`free` space in group-local coordinates, so an absolute
+            // prev-slot would be a meaningless offset there; only pass the
+            // hint in at the root and let nested groups fall back to plain
+            // buddy allocation.
+            let (placement, group_relocated) = merge_layouts(
+                s,
+                mem::replace(&mut children_variables[i], Vec::new()),
+                if i == 0 { prev_map.as_ref() } else { None },
+                if barycenter_sweeps > 0 {
+                    Some(&neighbor_slot)
+                } else {
+                    None
+                },
+                barycenter_sweeps,
+            );
+            placements[i] = placement;
+            relocated += group_relocated;
         }
 
         // now placements[0] contains all direct variables
@@ -353,14 +454,18 @@ impl<'a, C: Config> CompileContext<'a, C> {
 
         if lc.middle_sub_circuits.is_empty() {
             self.circuits.insert(req.circuit_id.clone(), ic);
-            return LayerLayout {
-                circuit_id: req.circuit_id,
-                layer: req.layer,
-                size: placements[0].len(),
-                inner: LayerLayoutInner::Dense {
-                    placement: placements.swap_remove(0),
+            return (
+                LayerLayout {
+                    circuit_id: req.circuit_id,
+                    layer: req.layer,
+                    size: placements[0].len(),
+                    version: prev_version,
+                    inner: LayerLayoutInner::Dense {
+                        placement: placements.swap_remove(0),
+                    },
                 },
-            };
+                relocated,
+            );
         }
 
         let mut middle_layouts = Vec::with_capacity(lc.middle_sub_circuits.len());
@@ -391,51 +496,371 @@ impl<'a, C: Config> CompileContext<'a, C> {
             }
             return i.cmp(&j);
         });
+
+        // greedily bin the blocks (the direct-variable block plus each middle
+        // sub-layout) by occupancy bitmask: a block joins the first existing
+        // bin whose mask is disjoint from it, instead of always opening a new
+        // offset range. blocks sharing a bin overlap at the same offset, which
+        // shrinks the padded layer size whenever several middle sub-layouts are
+        // sparse enough to be complementary.
+        let mut bins: Vec<(usize, Vec<bool>)> = Vec::new(); // (size, occupancy mask)
+        let mut bin_of: Vec<Option<usize>> = Vec::with_capacity(order.len());
+        for &i in order.iter() {
+            let mask = if i == 0 {
+                let mut m = vec![false; sizes[0]];
+                for (j, &x) in placements[0].iter().enumerate() {
+                    if x != EMPTY {
+                        m[j] = true;
+                    }
+                }
+                m
+            } else {
+                occupied_slots(&self.layer_layout_pool, middle_layouts[i - 1])
+            };
+            if i == 0 && !mask.iter().any(|&occ| occ) {
+                bin_of.push(None);
+                continue;
+            }
+            let joined = bins.iter().position(|(bsize, bmask)| {
+                mask.len() <= *bsize && (0..mask.len()).all(|j| !(mask[j] && bmask[j]))
+            });
+            match joined {
+                Some(bi) => {
+                    for (j, &occ) in mask.iter().enumerate() {
+                        if occ {
+                            bins[bi].1[j] = true;
+                        }
+                    }
+                    bin_of.push(Some(bi));
+                }
+                None => {
+                    bin_of.push(Some(bins.len()));
+                    bins.push((mask.len(), mask));
+                }
+            }
+        }
+
+        let mut bin_offset = Vec::with_capacity(bins.len());
         let mut cur = 0;
+        for (bsize, _) in bins.iter() {
+            bin_offset.push(cur);
+            cur += bsize;
+        }
+
         let mut placement_sparse = HashMap::new();
         let mut sub_layout = Vec::new();
-        for &i in order.iter() {
+        // assembly-time check backing the mask-disjointness invariant the
+        // binning above relies on: two blocks sharing a bin must never write
+        // the same absolute slot with a non-EMPTY variable. Kept as a real,
+        // always-on check (not `debug_assert!`) since a mask bug here would
+        // otherwise silently produce a colliding layout in release builds.
+        let mut assigned = vec![false; cur];
+        for (k, &i) in order.iter().enumerate() {
+            let bi = match bin_of[k] {
+                Some(bi) => bi,
+                None => continue,
+            };
+            let off = bin_offset[bi];
             if i == 0 {
-                let mut flag = false;
                 for (j, &x) in placements[0].iter().enumerate() {
                     if x != EMPTY {
-                        flag = true;
-                        placement_sparse.insert(cur + j, x);
+                        assert!(!assigned[off + j], "solve_layer_layout_normal: slot {} written by both the direct-variable block and another block", off + j);
+                        assigned[off + j] = true;
+                        placement_sparse.insert(off + j, x);
                     }
                 }
-                if !flag {
-                    continue;
-                }
             } else {
+                let sub_mask = occupied_slots(&self.layer_layout_pool, middle_layouts[i - 1]);
+                for (j, &occ) in sub_mask.iter().enumerate() {
+                    if occ {
+                        assert!(
+                            !assigned[off + j],
+                            "solve_layer_layout_normal: slot {} written by two overlapping sub-layouts",
+                            off + j
+                        );
+                        assigned[off + j] = true;
+                    }
+                }
                 sub_layout.push(SubLayout {
                     id: middle_layouts[i - 1],
-                    offset: cur,
+                    offset: off,
                     insn_id: ic.sub_circuit_insn_ids[lc.middle_sub_circuits[i - 1]],
                 });
             }
-            cur += sizes[i];
         }
         let size = next_power_of_two(cur);
 
         self.circuits.insert(req.circuit_id.clone(), ic);
-        LayerLayout {
-            circuit_id: req.circuit_id,
-            layer: req.layer,
-            size,
-            inner: LayerLayoutInner::Sparse {
-                placement: placement_sparse,
-                sub_layout,
+        (
+            LayerLayout {
+                circuit_id: req.circuit_id,
+                layer: req.layer,
+                size,
+                version: prev_version,
+                inner: LayerLayoutInner::Sparse {
+                    placement: placement_sparse,
+                    sub_layout,
+                },
             },
+            relocated,
+        )
+    }
+}
+
+// on-disk format for a solved `LayerLayout` pool, so repeated compiles of the
+// same circuit can load a cache instead of re-solving every layer.
+const LAYER_LAYOUT_CACHE_FORMAT_VERSION: u64 = 1;
+
+#[derive(Serialize, Deserialize)]
+struct LayerLayoutCacheFile {
+    format_version: u64,
+    // fingerprint of the circuit/config this cache was solved for; a cache
+    // whose fingerprint doesn't match the current compile is discarded
+    // wholesale rather than partially reused.
+    fingerprint: u64,
+    layouts: Vec<LayerLayout>,
+    req_to_layout: Vec<(LayerReq, usize)>,
+}
+
+impl<'a, C: Config> CompileContext<'a, C> {
+    // persists the current layer-layout pool plus the `LayerReq -> id` map to
+    // `path`, tagged with `fingerprint` so a later `load_layer_layout_cache`
+    // can tell whether it's still valid for the circuit being compiled.
+    pub fn save_layer_layout_cache(&self, path: &Path, fingerprint: u64) -> io::Result<()> {
+        let file = LayerLayoutCacheFile {
+            format_version: LAYER_LAYOUT_CACHE_FORMAT_VERSION,
+            fingerprint,
+            layouts: self.layer_layout_pool.vec().clone(),
+            req_to_layout: self
+                .layer_req_to_layout
+                .iter()
+                .map(|(req, &id)| (req.clone(), id))
+                .collect(),
+        };
+        let bytes = bincode::serialize(&file)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        fs::write(path, bytes)
+    }
+
+    // loads a previously saved cache from `path`, validating its format
+    // version and fingerprint against the current compile. Returns `Ok(true)`
+    // when the cache was loaded and merged into the in-memory pool (so
+    // `solve_layer_layout` can hit it directly), `Ok(false)` when there was
+    // nothing usable (missing file, stale format, a fingerprint mismatch from
+    // a different circuit/config, or this `CompileContext` already has
+    // layouts solved) - in which case every layer is simply solved from
+    // scratch, same as a fresh `CompileContext`.
+    //
+    // only valid to call before any `solve_layer_layout*` call on this
+    // context: re-inserting reproduces the *original* pool ids only when
+    // `Pool::add` is starting from empty, so a non-empty pool would leave
+    // `req_to_layout` pointing at whichever layouts happened to already
+    // occupy those ids.
+    pub fn load_layer_layout_cache(&mut self, path: &Path, fingerprint: u64) -> io::Result<bool> {
+        if !self.layer_layout_pool.vec().is_empty() || !self.layer_req_to_layout.is_empty() {
+            return Ok(false);
+        }
+        let bytes = match fs::read(path) {
+            Ok(b) => b,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(false),
+            Err(e) => return Err(e),
+        };
+        let file: LayerLayoutCacheFile = match bincode::deserialize(&bytes) {
+            Ok(f) => f,
+            Err(_) => return Ok(false),
+        };
+        if file.format_version != LAYER_LAYOUT_CACHE_FORMAT_VERSION || file.fingerprint != fingerprint
+        {
+            return Ok(false);
+        }
+        // re-inserting in the original order reproduces the original ids,
+        // since `Pool::add` only allocates a new id for content it hasn't
+        // seen before, and we've just confirmed the pool starts empty.
+        for layout in file.layouts.iter() {
+            self.layer_layout_pool.add(layout);
+        }
+        for (req, id) in file.req_to_layout {
+            self.layer_req_to_layout.insert(req, id);
+        }
+        Ok(true)
+    }
+}
+
+// pops a free block of the given order, splitting a larger free block downward
+// when none is available at that order, pushing the unused buddies back onto
+// their own free lists.
+fn buddy_alloc(free: &mut [Vec<usize>], order: usize) -> Option<usize> {
+    if let Some(off) = free[order].pop() {
+        return Some(off);
+    }
+    for higher in order + 1..free.len() {
+        if let Some(off) = free[higher].pop() {
+            let mut cur_order = higher;
+            let mut cur_off = off;
+            while cur_order > order {
+                cur_order -= 1;
+                free[cur_order].push(cur_off + (1 << cur_order));
+            }
+            return Some(cur_off);
         }
     }
+    None
+}
+
+// takes the aligned `2^order`-sized block at exactly `offset` out of the free
+// tree if it's still free, whether it is listed at this order or lies inside
+// a larger free block (which `buddy_reserve` then splits down). Used to honor
+// a variable's previous slot; `None` when that block is no longer free.
+fn buddy_alloc_at(free: &mut [Vec<usize>], order: usize, offset: usize) -> Option<usize> {
+    let is_free = (order..free.len()).any(|o| {
+        let block = 1usize << o;
+        free[o].contains(&(offset - offset % block))
+    });
+    if !is_free {
+        return None;
+    }
+    buddy_reserve(free, order, offset);
+    Some(offset)
 }
 
-fn merge_layouts(s: Vec<Vec<usize>>, additional: Vec<usize>) -> Vec<usize> {
-    // currently it's a simple greedy algorithm
-    // sort groups by size, and then place them one by one
-    // since their size are always 2^n, the result is aligned
-    // finally we insert the remaining variables to the empty slots
-    // TODO: improve this
+// marks the aligned `2^order`-sized block at `offset` as allocated, splitting
+// an ancestor free block down through the buddy tree if `offset` falls inside
+// one that hasn't been divided yet (pushing the unused buddies back onto
+// their own free lists, same as `buddy_alloc`'s split path in reverse). Used
+// to keep the buddy tree consistent with slots the hole-group first-fit scan
+// placed directly into `res`, so a later dense group can't be buddy-allocated
+// on top of them. A no-op when the block is already absent from the free
+// tree, which legitimately happens when an earlier hole-group first-fit
+// placement already reserved (or overlap-shares) the same span.
+fn buddy_reserve(free: &mut [Vec<usize>], order: usize, offset: usize) {
+    if let Some(pos) = free[order].iter().position(|&o| o == offset) {
+        free[order].swap_remove(pos);
+        return;
+    }
+    for higher in order + 1..free.len() {
+        let block = 1usize << higher;
+        let ancestor = offset - offset % block;
+        if let Some(pos) = free[higher].iter().position(|&o| o == ancestor) {
+            free[higher].swap_remove(pos);
+            let mut cur_order = higher;
+            let mut cur_off = ancestor;
+            while cur_order > order {
+                cur_order -= 1;
+                let half = 1usize << cur_order;
+                if offset < cur_off + half {
+                    free[cur_order].push(cur_off + half);
+                } else {
+                    free[cur_order].push(cur_off);
+                    cur_off += half;
+                }
+            }
+            return;
+        }
+    }
+}
+
+// Sugiyama-style barycenter ordering: sorts the free direct variables by the
+// slot they occupied in an already-placed neighboring layer (`neighbor_slot`),
+// so variables that were adjacent there stay adjacent once handed out to free
+// slots here. Variables with no neighbor hint sort last, keeping today's
+// arbitrary placement as the fallback. Runs up to `sweeps` passes, stopping
+// early once an ordering stops changing.
+fn barycenter_order(vars: &mut [usize], neighbor_slot: &HashMap<usize, usize>, sweeps: usize) {
+    if sweeps == 0 || vars.len() <= 1 {
+        return;
+    }
+    for _ in 0..sweeps {
+        let before = vars.to_vec();
+        vars.sort_by_key(|v| neighbor_slot.get(v).copied().unwrap_or(usize::MAX));
+        if vars == before.as_slice() {
+            break;
+        }
+    }
+}
+
+// same idea as `barycenter_order`, but applied to the members of a single
+// placement group (which may hold `EMPTY` holes alongside real variables):
+// EMPTY slots sort last, right after hinted variables and unhinted ones.
+fn barycenter_order_group(pg: &mut [usize], neighbor_slot: &HashMap<usize, usize>, sweeps: usize) {
+    if sweeps == 0 || pg.len() <= 1 {
+        return;
+    }
+    for _ in 0..sweeps {
+        let before = pg.to_vec();
+        pg.sort_by_key(|&v| {
+            if v == EMPTY {
+                usize::MAX
+            } else {
+                neighbor_slot.get(&v).copied().unwrap_or(usize::MAX - 1)
+            }
+        });
+        if pg == before.as_slice() {
+            break;
+        }
+    }
+}
+
+// mean neighbor-slot hint across a group's real variables, used to order
+// whole groups (of equal size) among themselves so related groups are
+// processed - and so tend to land at nearby offsets - next to each other.
+// `None` when no member carries a hint.
+fn group_barycenter(pg: &[usize], neighbor_slot: &HashMap<usize, usize>) -> Option<usize> {
+    let mut sum = 0usize;
+    let mut count = 0usize;
+    for &v in pg {
+        if v != EMPTY {
+            if let Some(&slot) = neighbor_slot.get(&v) {
+                sum += slot;
+                count += 1;
+            }
+        }
+    }
+    if count == 0 {
+        None
+    } else {
+        Some(sum / count)
+    }
+}
+
+// var (local index within the current `lc.vars`) -> absolute slot, inverting
+// whichever placement representation `layout` uses.
+fn layout_placement_map(layout: &LayerLayout) -> HashMap<usize, usize> {
+    let mut m = HashMap::new();
+    match &layout.inner {
+        LayerLayoutInner::Dense { placement } => {
+            for (i, &v) in placement.iter().enumerate() {
+                if v != EMPTY {
+                    m.insert(v, i);
+                }
+            }
+        }
+        LayerLayoutInner::Sparse { placement, .. } => {
+            for (&slot, &v) in placement.iter() {
+                m.insert(v, slot);
+            }
+        }
+    }
+    m
+}
+
+// returns the merged layout plus the number of variables placed at a slot
+// other than the one recorded in `prev`, when `prev` is given.
+fn merge_layouts(
+    s: Vec<Vec<usize>>,
+    mut additional: Vec<usize>,
+    prev: Option<&HashMap<usize, usize>>,
+    neighbor_slot: Option<&HashMap<usize, usize>>,
+    barycenter_sweeps: usize,
+) -> (Vec<usize>, usize) {
+    if let Some(ns) = neighbor_slot {
+        barycenter_order(&mut additional, ns, barycenter_sweeps);
+    }
+    // sort groups by size, then place them one by one.
+    // every group's size is a power of 2, so a buddy allocator keyed by block
+    // order (log2 of size) places it in O(log n) instead of scanning every
+    // aligned offset. groups that carry internal EMPTY holes still go through
+    // the old first-fit scan, since those can be overlap-merged against other
+    // sparse groups in a way a pure buddy allocator can't express.
     let mut n = 0;
     for x in s.iter() {
         let m = x.len();
@@ -445,7 +870,24 @@ fn merge_layouts(s: Vec<Vec<usize>>, additional: Vec<usize>) -> Vec<usize> {
         }
     }
     n = next_power_of_two(n);
-    let mut res = Vec::with_capacity(n);
+    let mut res = vec![EMPTY; n];
+    let mut relocated = 0;
+
+    let record_relocation = |v: usize, off: usize, relocated: &mut usize| {
+        if let Some(m) = prev {
+            if let Some(&old) = m.get(&v) {
+                if old != off {
+                    *relocated += 1;
+                }
+            }
+        }
+    };
+
+    let max_order = if n == 0 { 0 } else { n.trailing_zeros() as usize };
+    let mut free: Vec<Vec<usize>> = vec![Vec::new(); max_order + 1];
+    if n > 0 {
+        free[max_order].push(0);
+    }
 
     let mut order = Vec::with_capacity(s.len());
     for i in 0..s.len() {
@@ -457,48 +899,113 @@ fn merge_layouts(s: Vec<Vec<usize>>, additional: Vec<usize>) -> Vec<usize> {
         if s[i].len() != s[j].len() {
             return s[j].len().cmp(&s[i].len());
         }
+        // among equal-size peers, process groups with related barycenter
+        // hints together so they tend to claim nearby offsets.
+        if let Some(ns) = neighbor_slot {
+            let ki = group_barycenter(&s[i], ns).unwrap_or(usize::MAX);
+            let kj = group_barycenter(&s[j], ns).unwrap_or(usize::MAX);
+            if ki != kj {
+                return ki.cmp(&kj);
+            }
+        }
         return i.cmp(&j);
     });
 
+    // reordering a group's own members only changes which local offset each
+    // of its variables is assigned within the group, not the set of groups
+    // or their sizes, so it's safe to do right before each group is placed.
+    let mut reordered: HashMap<usize, Vec<usize>> = HashMap::new();
+    if let Some(ns) = neighbor_slot {
+        for &x_ in order.iter() {
+            let mut pg = s[x_].clone();
+            barycenter_order_group(&mut pg, ns, barycenter_sweeps);
+            reordered.insert(x_, pg);
+        }
+    }
+
     for x_ in order.iter() {
-        let pg = &s[*x_];
-        if res.len() % pg.len() != 0 {
-            panic!("unexpected situation");
-        }
-        let mut placed = false;
-        // TODO: better collision detection
-        for i in (0..res.len()).step_by(pg.len()) {
-            let mut ok = true;
-            for j in 0..pg.len() {
-                if res[i + j] != EMPTY && pg[j] != EMPTY {
-                    ok = false;
+        let pg = reordered.get(x_).unwrap_or(&s[*x_]);
+        if pg.iter().any(|&v| v == EMPTY) {
+            // has internal holes: fall back to the collision-aware first-fit scan
+            let mut placed = false;
+            for i in (0..res.len()).step_by(pg.len()) {
+                let mut ok = true;
+                for j in 0..pg.len() {
+                    if res[i + j] != EMPTY && pg[j] != EMPTY {
+                        ok = false;
+                        break;
+                    }
+                }
+                if ok {
+                    for j in 0..pg.len() {
+                        if pg[j] != EMPTY {
+                            res[i + j] = pg[j];
+                            record_relocation(pg[j], i + j, &mut relocated);
+                        }
+                    }
+                    // `i` is a multiple of `pg.len()` (a power of 2) since we
+                    // stepped by it over a power-of-2-sized `res`, so it's
+                    // always an aligned buddy-tree node; keep the buddy
+                    // allocator from later handing this span to a dense group.
+                    buddy_reserve(&mut free, pg.len().trailing_zeros() as usize, i);
+                    placed = true;
                     break;
                 }
             }
-            if ok {
+            if !placed {
+                let k = pg.len().trailing_zeros() as usize;
+                let off = buddy_alloc(&mut free, k).expect("buddy allocator ran out of space");
                 for j in 0..pg.len() {
                     if pg[j] != EMPTY {
-                        res[i + j] = pg[j];
+                        res[off + j] = pg[j];
+                        record_relocation(pg[j], off + j, &mut relocated);
                     }
                 }
-                placed = true;
-                break;
             }
-        }
-        if !placed {
-            res.extend_from_slice(pg);
+        } else {
+            let k = pg.len().trailing_zeros() as usize;
+            // prefer the previous slot of this group's first variable, as long
+            // as it's still free at the alignment this group needs
+            let preferred = prev.and_then(|m| {
+                pg.iter().find_map(|&v| m.get(&v)).copied().and_then(|off| {
+                    if off % pg.len() == 0 {
+                        buddy_alloc_at(&mut free, k, off)
+                    } else {
+                        None
+                    }
+                })
+            });
+            let off = preferred
+                .or_else(|| buddy_alloc(&mut free, k))
+                .expect("buddy allocator ran out of space");
+            res[off..off + pg.len()].copy_from_slice(pg);
+            for (j, &v) in pg.iter().enumerate() {
+                if v != EMPTY {
+                    record_relocation(v, off + j, &mut relocated);
+                }
+            }
         }
     }
 
-    let mut slot = 0;
+    // the trailing single-variable fill just drains the order-0 free list,
+    // preferring each variable's previous slot when it's still free. when
+    // barycenter ordering is on, `additional` is already sorted by neighbor
+    // slot, so draining the free list low-to-high instead of in whatever
+    // order splitting happened to leave it keeps that ordering meaningful.
+    if neighbor_slot.is_some() {
+        free[0].sort_unstable_by(|a, b| b.cmp(a));
+    }
     for x in additional.iter() {
-        while slot < res.len() && res[slot] != EMPTY {
-            slot += 1;
-        }
-        if slot >= res.len() {
-            res.push(*x);
-        } else {
-            res[slot] = *x;
+        let preferred = prev
+            .and_then(|m| m.get(x))
+            .copied()
+            .and_then(|off| buddy_alloc_at(&mut free, 0, off));
+        match preferred.or_else(|| buddy_alloc(&mut free, 0)) {
+            Some(off) => {
+                res[off] = *x;
+                record_relocation(*x, off, &mut relocated);
+            }
+            None => res.push(*x),
         }
     }
 
@@ -507,7 +1014,40 @@ fn merge_layouts(s: Vec<Vec<usize>>, additional: Vec<usize>) -> Vec<usize> {
         res.push(EMPTY);
     }
 
-    res
+    (res, relocated)
+}
+
+// computes which absolute slots of `layout` are non-EMPTY, recursing into any
+// nested sub-layouts so overlapping sparse blocks can be detected by mask.
+fn occupied_slots(pool: &Pool<LayerLayout>, id: usize) -> Vec<bool> {
+    let layout = pool.get(id);
+    let mut mask = vec![false; layout.size];
+    match &layout.inner {
+        LayerLayoutInner::Dense { placement } => {
+            for (i, &v) in placement.iter().enumerate() {
+                if v != EMPTY {
+                    mask[i] = true;
+                }
+            }
+        }
+        LayerLayoutInner::Sparse {
+            placement,
+            sub_layout,
+        } => {
+            for &k in placement.keys() {
+                mask[k] = true;
+            }
+            for sl in sub_layout.iter() {
+                let sub_mask = occupied_slots(pool, sl.id);
+                for (j, &occ) in sub_mask.iter().enumerate() {
+                    if occ {
+                        mask[sl.offset + j] = true;
+                    }
+                }
+            }
+        }
+    }
+    mask
 }
 
 fn subs_array(l: &mut Vec<usize>, s: &Vec<usize>) {
@@ -530,3 +1070,49 @@ pub fn subs_map(l: &mut Vec<usize>, m: &HashMap<usize, usize>) {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // a hole-group placed by first-fit must not leave its span visible to the
+    // buddy allocator, or a later dense group can land on top of it.
+    #[test]
+    fn merge_layouts_hole_group_does_not_alias_buddy_group() {
+        let (v0, v1, v2) = (10, 11, 12);
+        let (w0, w1, w2, w3) = (20, 21, 22, 23);
+        let groups = vec![vec![v0, EMPTY, v1, v2], vec![w0, w1, w2, w3]];
+        let (res, _) = merge_layouts(groups, Vec::new(), None, None, 0);
+
+        let mut slot_of = HashMap::new();
+        for (slot, &v) in res.iter().enumerate() {
+            if v != EMPTY {
+                assert!(
+                    slot_of.insert(v, slot).is_none(),
+                    "variable {v} placed twice"
+                );
+            }
+        }
+        for v in [v0, v1, v2, w0, w1, w2, w3] {
+            assert!(slot_of.contains_key(&v), "variable {v} missing from layout");
+        }
+    }
+
+    // barycenter ordering should both reorder equal-size groups among
+    // themselves and reorder each group's own members, by the slot their
+    // variables occupied in a neighboring already-placed layer.
+    #[test]
+    fn merge_layouts_barycenter_reorders_groups_and_members() {
+        let (v0, v1, v2, v3) = (10, 11, 12, 13);
+        let groups = vec![vec![v0, v1], vec![v2, v3]];
+        let neighbor_slot: HashMap<usize, usize> =
+            [(v0, 101), (v1, 100), (v2, 2), (v3, 1)].into_iter().collect();
+
+        let (res, _) = merge_layouts(groups, Vec::new(), None, Some(&neighbor_slot), 1);
+
+        // group [v2, v3] has the lower mean neighbor slot, so it's processed
+        // (and thus placed) first; within each group, the lower-hinted
+        // member comes first.
+        assert_eq!(res, vec![v3, v2, v1, v0]);
+    }
+}